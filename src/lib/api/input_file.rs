@@ -0,0 +1,77 @@
+use serde::{Serialize, Serializer};
+
+/// A file to send with methods like `sendVoice`, `sendPhoto` or `sendDocument`.
+#[derive(Debug, Clone)]
+pub enum InputFile {
+    /// A `file_id` already known to Telegram.
+    FileId(String),
+
+    /// An `http(s)://` URL Telegram will fetch the file from.
+    Url(String),
+
+    /// Raw bytes to upload directly, with a file name for the multipart field. Not
+    /// representable as a plain JSON string, so it serializes as `attach://<name>`
+    /// per the Bot API's multipart convention; [`InputFile::attachment`] is what
+    /// actually pulls the bytes out to build that multipart part.
+    Bytes { name: String, data: Vec<u8> },
+}
+
+impl InputFile {
+    /// The multipart field name and raw bytes to attach for this file, or `None` for
+    /// a `file_id`/URL that's sent as a plain JSON string with no attached part.
+    pub fn attachment(&self) -> Option<(&str, &[u8])> {
+        match self {
+            Self::Bytes { name, data } => Some((name.as_str(), data.as_slice())),
+            _ => None,
+        }
+    }
+}
+
+impl Serialize for InputFile {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Self::FileId(id) => serializer.serialize_str(id),
+            Self::Url(url) => serializer.serialize_str(url),
+            Self::Bytes { name, .. } => serializer.serialize_str(&format!("attach://{name}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_id_and_url_serialize_as_plain_strings() {
+        assert_eq!(
+            serde_json::to_string(&InputFile::FileId("abc123".to_string())).unwrap(),
+            "\"abc123\""
+        );
+        assert_eq!(
+            serde_json::to_string(&InputFile::Url("https://example.com/a.ogg".to_string())).unwrap(),
+            "\"https://example.com/a.ogg\""
+        );
+    }
+
+    #[test]
+    fn bytes_serializes_as_an_attach_url_instead_of_failing() {
+        let file = InputFile::Bytes {
+            name: "note.ogg".to_string(),
+            data: vec![1, 2, 3],
+        };
+        assert_eq!(
+            serde_json::to_string(&file).unwrap(),
+            "\"attach://note.ogg\""
+        );
+        assert_eq!(file.attachment(), Some(("note.ogg", &[1u8, 2, 3][..])));
+    }
+
+    #[test]
+    fn file_id_and_url_have_no_attachment() {
+        assert_eq!(InputFile::FileId("abc".to_string()).attachment(), None);
+        assert_eq!(InputFile::Url("https://example.com".to_string()).attachment(), None);
+    }
+}
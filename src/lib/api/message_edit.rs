@@ -0,0 +1,71 @@
+use serde::Serialize;
+
+use crate::{api::Message, API};
+
+/// Parameters for the Bot API `editMessageText` method.
+#[derive(Debug, Clone, Serialize)]
+pub struct EditMessageTextRequest {
+    pub chat_id: i64,
+    pub message_id: i64,
+    pub text: String,
+}
+
+/// Parameters for the Bot API `editMessageText` method, with MarkdownV2 formatting.
+#[derive(Debug, Clone, Serialize)]
+pub struct EditMessageMarkdownRequest {
+    pub chat_id: i64,
+    pub message_id: i64,
+    pub text: String,
+    pub parse_mode: &'static str,
+}
+
+/// Parameters for the Bot API `deleteMessage` method.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeleteMessageRequest {
+    pub chat_id: i64,
+    pub message_id: i64,
+}
+
+impl API {
+    /// Replaces the text of a message the bot previously sent, via `editMessageText`.
+    pub async fn edit_message_text(
+        &self,
+        chat_id: i64,
+        message_id: i64,
+        text: String,
+    ) -> Result<Message, anyhow::Error> {
+        let request = EditMessageTextRequest {
+            chat_id,
+            message_id,
+            text,
+        };
+        self.call("editMessageText", &request).await
+    }
+
+    /// Same as [`API::edit_message_text`], but with MarkdownV2 formatting. Make sure
+    /// to escape any user input!
+    pub async fn edit_message_markdown(
+        &self,
+        chat_id: i64,
+        message_id: i64,
+        text: String,
+    ) -> Result<Message, anyhow::Error> {
+        let request = EditMessageMarkdownRequest {
+            chat_id,
+            message_id,
+            text,
+            parse_mode: "MarkdownV2",
+        };
+        self.call("editMessageText", &request).await
+    }
+
+    /// Deletes a message the bot previously sent, via `deleteMessage`.
+    pub async fn delete_message(&self, chat_id: i64, message_id: i64) -> Result<(), anyhow::Error> {
+        let request = DeleteMessageRequest {
+            chat_id,
+            message_id,
+        };
+        self.call::<_, bool>("deleteMessage", &request).await?;
+        Ok(())
+    }
+}
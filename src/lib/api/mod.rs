@@ -0,0 +1,6 @@
+pub mod input_file;
+pub mod message_edit;
+pub mod voice;
+
+pub use input_file::InputFile;
+pub use voice::Voice;
@@ -1,5 +1,10 @@
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    api::{InputFile, Message},
+    API,
+};
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Voice {
     pub duration: u32,
@@ -8,3 +13,41 @@ pub struct Voice {
     pub file_unique_id: String,
     pub file_size: u32,
 }
+
+/// Parameters for the Bot API `sendVoice` method.
+#[derive(Debug, Clone, Serialize)]
+pub struct SendVoiceRequest {
+    pub chat_id: i64,
+    pub voice: InputFile,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub caption: Option<String>,
+}
+
+impl API {
+    /// Sends a voice note via the Bot API's `sendVoice` method.
+    ///
+    /// Only [`InputFile::FileId`] and [`InputFile::Url`] are supported for now,
+    /// since both need no attached part and go out as a plain JSON call.
+    /// [`InputFile::Bytes`] would need a real multipart upload of the attached part
+    /// named by [`InputFile::attachment`], which doesn't exist yet; it's rejected
+    /// with an error rather than silently sent as a broken `attach://` reference.
+    pub async fn send_voice(
+        &self,
+        chat_id: i64,
+        file: InputFile,
+        caption: Option<String>,
+    ) -> Result<Message, anyhow::Error> {
+        if file.attachment().is_some() {
+            return Err(anyhow::anyhow!(
+                "send_voice: InputFile::Bytes needs a multipart upload, which isn't implemented yet; use FileId or Url instead"
+            ));
+        }
+
+        let request = SendVoiceRequest {
+            chat_id,
+            voice: file,
+            caption,
+        };
+        self.call("sendVoice", &request).await
+    }
+}
@@ -1,19 +1,142 @@
-use std::sync::Arc;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use futures::{future::BoxFuture, Future};
-use tokio::sync::RwLock;
+use tokio::sync::{oneshot, Mutex, RwLock};
 
 use crate::{
-    api::{CallbackQuery, Message, Update},
+    api::{CallbackQuery, Chat, InputFile, Message, Update, Voice},
     API,
 };
 
+/// Monotonic id used to find a specific queued [`get_reply`](Event::get_reply) slot
+/// again, since a chat's queue can hold more than one waiting call at once.
+static NEXT_AWAITER_ID: AtomicU64 = AtomicU64::new(0);
+
+type ReplyAwaiters = HashMap<i64, VecDeque<(u64, oneshot::Sender<MessageEvent>)>>;
+
+/// Shared registry of handlers blocked in [`Event::get_reply`], keyed by chat id.
+///
+/// The router consults this before running the normal handler chain: if the chat an
+/// update belongs to has a pending awaiter, the update is delivered straight to the
+/// oldest one via [`ReplyRegistry::try_deliver`] instead of being dispatched as usual.
+#[derive(Clone, Default)]
+pub struct ReplyRegistry(Arc<Mutex<ReplyAwaiters>>);
+
+impl ReplyRegistry {
+    /// Delivers `event` to the oldest awaiter registered for `chat_id`, if any.
+    ///
+    /// Only `MessageEvent::New` is ever delivered this way, matching the contract
+    /// `Event::get_reply` documents ("the next `MessageEvent::New`"); any other
+    /// variant is left for normal dispatch.
+    ///
+    /// Returns `true` if an awaiter took the event, meaning the caller should skip
+    /// normal dispatch for this update.
+    pub async fn try_deliver(&self, chat_id: i64, event: MessageEvent) -> bool {
+        if !matches!(event, MessageEvent::New(_)) {
+            return false;
+        }
+
+        let mut awaiters = self.0.lock().await;
+        if let Some(queue) = awaiters.get_mut(&chat_id) {
+            while let Some((_, tx)) = queue.pop_front() {
+                match tx.send(event.clone()) {
+                    Ok(()) => return true,
+                    // The waiting future was already dropped; try the next awaiter.
+                    Err(_) => continue,
+                }
+            }
+        }
+        false
+    }
+
+    async fn register(&self, chat_id: i64) -> (ReplySlot, oneshot::Receiver<MessageEvent>) {
+        let id = NEXT_AWAITER_ID.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        self.0
+            .lock()
+            .await
+            .entry(chat_id)
+            .or_default()
+            .push_back((id, tx));
+        (
+            ReplySlot {
+                chat_id,
+                id,
+                registry: self.clone(),
+            },
+            rx,
+        )
+    }
+
+    async fn forget(&self, chat_id: i64, id: u64) {
+        let mut awaiters = self.0.lock().await;
+        if let Some(queue) = awaiters.get_mut(&chat_id) {
+            queue.retain(|(awaiter_id, _)| *awaiter_id != id);
+            if queue.is_empty() {
+                awaiters.remove(&chat_id);
+            }
+        }
+    }
+}
+
+/// Tracks, per chat, the message id of the bot's most recent reply, so
+/// `Action::EditLastReply` can retarget it without the handler threading ids around
+/// itself. The dispatcher records into this after every reply it sends.
+#[derive(Clone, Default)]
+pub struct SentMessageTracker(Arc<Mutex<HashMap<i64, i64>>>);
+
+impl SentMessageTracker {
+    /// Records `message_id` as the most recent message the bot sent to `chat_id`.
+    pub async fn record(&self, chat_id: i64, message_id: i64) {
+        self.0.lock().await.insert(chat_id, message_id);
+    }
+
+    /// The most recent message id the bot sent to `chat_id`, if any.
+    pub async fn last(&self, chat_id: i64) -> Option<i64> {
+        self.0.lock().await.get(&chat_id).copied()
+    }
+}
+
+/// Drop guard for a queued `get_reply` slot. Removing the sender on drop means a
+/// timed-out or cancelled wait never leaves a dangling awaiter behind.
+struct ReplySlot {
+    chat_id: i64,
+    id: u64,
+    registry: ReplyRegistry,
+}
+
+impl Drop for ReplySlot {
+    fn drop(&mut self) {
+        let registry = self.registry.clone();
+        let (chat_id, id) = (self.chat_id, self.id);
+        // `forget` is a no-op if the slot was already delivered and popped, so it is
+        // always safe to call here regardless of why the slot is being dropped.
+        tokio::spawn(async move {
+            registry.forget(chat_id, id).await;
+        });
+    }
+}
+
 #[derive(Clone, Default)]
 pub struct State<T: Clone> {
     state: Arc<RwLock<T>>,
 }
 
 impl<T: Clone> State<T> {
+    /// Wraps `value` in a freshly locked `State`, independent of any other `State`.
+    pub fn new(value: T) -> Self {
+        Self {
+            state: Arc::new(RwLock::new(value)),
+        }
+    }
+
     pub async fn from(&self) -> Self {
         Self {
             state: Arc::new(RwLock::new((*self.state.read().await).clone())),
@@ -30,6 +153,15 @@ impl<T: Clone> State<T> {
 pub struct Event {
     pub api: Arc<API>,
     pub message: MessageEvent,
+
+    /// Registry of handlers waiting on [`Event::get_reply`] for this event's chat.
+    /// Populated by the router so conversational handlers don't need their own
+    /// bookkeeping in `State<S>`.
+    pub(crate) replies: ReplyRegistry,
+
+    /// Tracks the bot's most recently sent message per chat, for
+    /// [`Event::last_sent_message_id`] and `Action::EditLastReply`.
+    pub(crate) sent_messages: SentMessageTracker,
 }
 
 /// `MessageEvent` represents a new or edited message.
@@ -90,14 +222,15 @@ impl From<MessageEvent> for CallbackQuery {
 impl ToString for MessageEvent {
     fn to_string(&self) -> String {
         match self {
-            Self::New(msg) => msg.text.clone().unwrap(),
-            Self::Edited(msg) => msg.text.clone().unwrap(),
-            Self::Post(msg) => msg.text.clone().unwrap(),
-            Self::EditedPost(msg) => msg.text.clone().unwrap(),
-            Self::Callback(query) => query.data.clone().unwrap(),
-            Self::Unknown => {
-                panic!("Bad MessageEvent::Unknown")
-            }
+            Self::New(msg) => msg.text.clone().unwrap_or_else(|| "<no text>".to_string()),
+            Self::Edited(msg) => msg.text.clone().unwrap_or_else(|| "<no text>".to_string()),
+            Self::Post(msg) => msg.text.clone().unwrap_or_else(|| "<no text>".to_string()),
+            Self::EditedPost(msg) => msg.text.clone().unwrap_or_else(|| "<no text>".to_string()),
+            Self::Callback(query) => query
+                .data
+                .clone()
+                .unwrap_or_else(|| "<no data>".to_string()),
+            Self::Unknown => "<unknown>".to_string(),
         }
     }
 }
@@ -160,6 +293,70 @@ impl Event {
             _ => Err(anyhow::anyhow!("MessageEvent is not a CallbackQuery")),
         }
     }
+
+    /// Get the voice note attached to this event's message, if any.
+    pub fn get_voice(&self) -> Result<&Voice, anyhow::Error> {
+        match self.get_message()?.voice {
+            Some(ref voice) => Ok(voice),
+            None => Err(anyhow::anyhow!("Message has no voice note")),
+        }
+    }
+
+    /// Suspends this handler until the next `MessageEvent::New` arrives from the same
+    /// chat, for building multi-step dialogs (e.g. "enter your name" -> "enter your
+    /// email") without a hand-rolled state machine in `State<S>`.
+    ///
+    /// The router delivers the next matching update straight to this call instead of
+    /// running the normal handler chain for it.
+    pub async fn get_reply(&self) -> Result<MessageEvent, anyhow::Error> {
+        let chat_id = self.chat_id()?;
+        let (_slot, rx) = self.replies.register(chat_id).await;
+        rx.await
+            .map_err(|_| anyhow::anyhow!("reply channel closed before a message arrived"))
+    }
+
+    /// Same as [`Event::get_reply`], but gives up after `timeout` instead of waiting
+    /// forever.
+    pub async fn get_reply_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<MessageEvent, anyhow::Error> {
+        let chat_id = self.chat_id()?;
+        let (_slot, rx) = self.replies.register(chat_id).await;
+        match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(event)) => Ok(event),
+            Ok(Err(_)) => Err(anyhow::anyhow!("reply channel closed before a message arrived")),
+            Err(_) => Err(anyhow::anyhow!("timed out waiting for a reply")),
+        }
+    }
+
+    /// The message id of the bot's most recent reply to this event's chat, for use
+    /// with `Action::EditMessage`/`Action::DeleteMessage` when `Action::EditLastReply`
+    /// isn't specific enough (e.g. editing a message other than the latest one).
+    pub async fn last_sent_message_id(&self) -> Result<i64, anyhow::Error> {
+        let chat_id = self.chat_id()?;
+        self.sent_messages
+            .last(chat_id)
+            .await
+            .ok_or_else(|| anyhow::anyhow!("no message has been sent to chat {} yet", chat_id))
+    }
+
+    /// The chat id a message, post or callback query in this event belongs to.
+    pub(crate) fn chat_id(&self) -> Result<i64, anyhow::Error> {
+        match &self.message {
+            MessageEvent::New(msg)
+            | MessageEvent::Edited(msg)
+            | MessageEvent::Post(msg)
+            | MessageEvent::EditedPost(msg) => Ok(msg.chat.id),
+            MessageEvent::Callback(query) => Ok(query
+                .message
+                .as_ref()
+                .ok_or_else(|| anyhow::anyhow!("CallbackQuery has no message"))?
+                .chat
+                .id),
+            MessageEvent::Unknown => Err(anyhow::anyhow!("MessageEvent is Unknown")),
+        }
+    }
 }
 
 /// `Action` represents an action to take after handling a chat event.
@@ -182,25 +379,47 @@ pub enum Action {
     /// Reply to the message with the given sticker and continue
     /// to the next handler.
     ReplySticker(String),
+
+    /// Reply to the message with the given voice note and continue
+    /// to the next handler.
+    ReplyVoice {
+        file: InputFile,
+        caption: Option<String>,
+    },
+
+    /// Edit a message the bot previously sent, replacing its text.
+    EditMessage { message_id: i64, text: String },
+
+    /// Same as EditMessage, but with MarkdownV2 formatting. Make
+    /// sure to escape any user input!
+    EditMarkdown { message_id: i64, text: String },
+
+    /// Delete a message the bot previously sent.
+    DeleteMessage { message_id: i64 },
+
+    /// Edit the last reply this chat's handler sent, without the handler needing to
+    /// track its id itself. See [`Event::last_sent_message_id`]. Fails if the chat
+    /// has no recorded reply yet.
+    EditLastReply(String),
 }
 
 /// A handler for a specific chat ID. This is a wrapper around an async function
 /// that takes a `ChatEvent` and returns a `ChatAction`.
+///
+/// A `Handler` carries no state of its own: when it runs as part of a
+/// [`PluginChain`](crate::PluginChain) driven by a
+/// [`ChatWorkerPool`](crate::ChatWorkerPool), the `State<S>` it's called with is
+/// seeded from that chat's own state for every call. Seed that per-chat state via
+/// `S::default()` or a [`StateStore`](crate::StateStore), not the handler.
 pub struct Handler<S: Clone> {
     /// Wraps the async handler function.
     #[allow(clippy::type_complexity)]
     pub f: Box<
         dyn Fn(Event, State<S>) -> BoxFuture<'static, Result<Action, anyhow::Error>> + Send + Sync,
     >,
-
-    /// State related to this Chat ID
-    pub state: State<S>,
 }
 
-impl<S: Clone> Handler<S>
-where
-    S: Default,
-{
+impl<S: Clone> Handler<S> {
     pub fn new<Func, Fut>(func: Func) -> Self
     where
         Func: Send + Sync + 'static + Fn(Event, State<S>) -> Fut,
@@ -208,30 +427,13 @@ where
     {
         Self {
             f: Box::new(move |a, b| Box::pin(func(a, b))),
-            state: State {
-                state: Arc::new(tokio::sync::RwLock::new(S::default())),
-            },
-        }
-    }
-
-    pub fn with_state(self, state: S) -> Self {
-        Self {
-            f: self.f,
-            state: State {
-                state: Arc::new(tokio::sync::RwLock::new(state)),
-            },
         }
     }
-
-    pub fn set_state(&mut self, state: Arc<RwLock<S>>) -> &mut Self {
-        self.state = State { state };
-        self
-    }
 }
 
 impl<S, Func, Fut> From<Func> for Handler<S>
 where
-    S: Default + Clone,
+    S: Clone,
     Func: Send + Sync + 'static + Fn(Event, State<S>) -> Fut,
     Fut: Send + 'static + Future<Output = Result<Action, anyhow::Error>>,
 {
@@ -240,6 +442,93 @@ where
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message_event(chat_id: i64) -> MessageEvent {
+        MessageEvent::New(Message {
+            chat: Chat {
+                id: chat_id,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn dropping_a_get_reply_wait_removes_its_awaiter() {
+        let registry = ReplyRegistry::default();
+        let (slot, rx) = registry.register(42).await;
+        assert_eq!(registry.0.lock().await.get(&42).map(VecDeque::len), Some(1));
+
+        // Simulate `get_reply`/`get_reply_timeout`'s future being dropped, e.g. on
+        // timeout or handler cancellation.
+        drop(rx);
+        drop(slot);
+
+        // `ReplySlot::drop` removes the awaiter on a spawned task; give it a chance
+        // to run before asserting.
+        for _ in 0..50 {
+            if registry.0.lock().await.get(&42).is_none() {
+                break;
+            }
+            tokio::task::yield_now().await;
+        }
+        assert!(
+            registry.0.lock().await.get(&42).is_none(),
+            "a dropped get_reply should not leave a dangling awaiter behind"
+        );
+    }
+
+    #[tokio::test]
+    async fn try_deliver_only_matches_message_event_new() {
+        let registry = ReplyRegistry::default();
+        let (_slot, rx) = registry.register(7).await;
+
+        // An edited message isn't a reply to a question the handler just asked; it
+        // must be left for normal dispatch instead of satisfying the awaiter.
+        let edited = MessageEvent::Edited(Message {
+            chat: Chat {
+                id: 7,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        assert!(!registry.try_deliver(7, edited).await);
+
+        let new = message_event(7);
+        assert!(registry.try_deliver(7, new).await);
+        assert!(matches!(rx.await, Ok(MessageEvent::New(_))));
+    }
+
+    #[tokio::test]
+    async fn sent_message_tracker_is_scoped_per_chat() {
+        // This is the correlation `Action::EditLastReply` relies on: the dispatcher
+        // records a reply's id here, and `Event::last_sent_message_id` reads it back
+        // for the same chat without the handler ever seeing an id itself.
+        let tracker = SentMessageTracker::default();
+        assert_eq!(tracker.last(1).await, None);
+
+        tracker.record(1, 100).await;
+        tracker.record(2, 200).await;
+        assert_eq!(tracker.last(1).await, Some(100));
+        assert_eq!(tracker.last(2).await, Some(200));
+
+        tracker.record(1, 101).await;
+        assert_eq!(
+            tracker.last(1).await,
+            Some(101),
+            "a newer reply should replace the chat's last recorded one"
+        );
+        assert_eq!(
+            tracker.last(2).await,
+            Some(200),
+            "recording a reply for one chat must not affect another chat's last id"
+        );
+    }
+}
+
 /// This handler logs every message received.
 pub async fn log_handler<S>(e: Event, _: S) -> Result<Action, anyhow::Error> {
     match e.message {
@@ -0,0 +1,7 @@
+pub mod chat;
+pub mod plugin;
+pub mod worker;
+
+pub use chat::*;
+pub use plugin::*;
+pub use worker::*;
@@ -0,0 +1,245 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use crate::{Action, Event, Handler, State};
+
+/// A named, priority-ordered handler that can be toggled on or off at runtime.
+///
+/// Wrapping a [`Handler`] this way lets larger bots layer auth/rate-limit/logging
+/// plugins ahead of feature handlers, inspect what's registered via
+/// [`PluginChain::active_plugins`], and disable one without recompiling (e.g. from an
+/// admin command).
+pub struct Plugin<S: Clone> {
+    /// Stable identifier for this plugin, used for introspection and toggling.
+    pub name: String,
+
+    /// Plugins run in ascending priority order; lower runs first. Ties keep
+    /// registration order.
+    pub priority: i32,
+
+    enabled: Arc<AtomicBool>,
+    handler: Handler<S>,
+}
+
+impl<S: Clone> Plugin<S> {
+    /// Wraps `handler` as a plugin named `name` at the given `priority`, enabled by
+    /// default.
+    pub fn new(name: impl Into<String>, priority: i32, handler: Handler<S>) -> Self {
+        Self {
+            name: name.into(),
+            priority,
+            enabled: Arc::new(AtomicBool::new(true)),
+            handler,
+        }
+    }
+
+    /// Whether this plugin currently runs as part of its chain.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Enables or disables this plugin. Takes effect on the next dispatched event.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// A cloneable handle for toggling this plugin's `enabled` flag from elsewhere
+    /// (e.g. an admin command handler) without holding onto the plugin itself.
+    pub fn toggle(&self) -> PluginToggle {
+        PluginToggle(self.enabled.clone())
+    }
+}
+
+/// A cloneable handle that reads or flips a single [`Plugin`]'s `enabled` flag.
+#[derive(Clone)]
+pub struct PluginToggle(Arc<AtomicBool>);
+
+impl PluginToggle {
+    /// Enables or disables the plugin this handle was created from.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.0.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Whether the plugin this handle was created from currently runs.
+    pub fn is_enabled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Holds a priority-ordered chain of [`Plugin`]s and runs them in turn.
+pub struct PluginChain<S: Clone> {
+    plugins: Vec<Plugin<S>>,
+}
+
+impl<S: Clone> Default for PluginChain<S> {
+    fn default() -> Self {
+        Self {
+            plugins: Vec::new(),
+        }
+    }
+}
+
+impl<S: Clone> PluginChain<S> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `plugin`, re-sorting the chain by ascending priority.
+    pub fn register(&mut self, plugin: Plugin<S>) -> &mut Self {
+        self.plugins.push(plugin);
+        self.plugins.sort_by_key(|p| p.priority);
+        self
+    }
+
+    /// The registered plugins' names and enabled state, in the order they run.
+    pub fn active_plugins(&self) -> Vec<(&str, bool)> {
+        self.plugins
+            .iter()
+            .map(|p| (p.name.as_str(), p.is_enabled()))
+            .collect()
+    }
+
+    /// Runs the chain for `event` against `state`, skipping disabled plugins in
+    /// priority order, and stopping at the first `Action::Done`.
+    ///
+    /// `state` is the chat's own state, owned by its [`ChatWorkerPool`](crate::ChatWorkerPool)
+    /// worker rather than shared across chats: each plugin runs against a fresh
+    /// `State` seeded with `state`'s current value, and whatever it leaves that
+    /// `State` holding is written back to `state` before the next plugin runs. A
+    /// plugin's `Handler` carries no state of its own — every plugin in the chain
+    /// reads and writes this same per-chat value in turn.
+    ///
+    /// Returns every action produced along the way, in order: a plugin's
+    /// `ReplyText`/`ReplyMarkdown`/`ReplySticker`/`ReplyVoice` means "reply *and*
+    /// continue", so a reply from a plugin in the middle of the chain must still
+    /// reach the caller, not just the last action before `Done`.
+    pub async fn dispatch(
+        &self,
+        event: Event,
+        state: &mut S,
+    ) -> Result<Vec<Action>, anyhow::Error> {
+        let mut actions = Vec::new();
+        for plugin in &self.plugins {
+            if !plugin.is_enabled() {
+                continue;
+            }
+            let chat_state = State::new(state.clone());
+            let action = (plugin.handler.f)(event.clone(), chat_state.clone()).await?;
+            *state = chat_state.get().read().await.clone();
+            let done = matches!(action, Action::Done);
+            actions.push(action);
+            if done {
+                break;
+            }
+        }
+        Ok(actions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::Mutex;
+
+    use super::*;
+    use crate::{
+        api::{Chat, Message},
+        API,
+    };
+
+    fn test_event(chat_id: i64) -> Event {
+        Event {
+            api: Arc::new(API::new("test-token".to_string())),
+            message: crate::MessageEvent::New(Message {
+                chat: Chat {
+                    id: chat_id,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            replies: crate::ReplyRegistry::default(),
+            sent_messages: crate::SentMessageTracker::default(),
+        }
+    }
+
+    fn recording_plugin(
+        name: &'static str,
+        priority: i32,
+        log: Arc<Mutex<Vec<&'static str>>>,
+    ) -> Plugin<()> {
+        Plugin::new(
+            name,
+            priority,
+            Handler::new(move |_event: Event, _state: State<()>| {
+                let log = log.clone();
+                async move {
+                    log.lock().await.push(name);
+                    Ok(Action::Next)
+                }
+            }),
+        )
+    }
+
+    #[tokio::test]
+    async fn plugins_run_in_priority_order_not_registration_order() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut chain = PluginChain::<()>::new();
+        chain.register(recording_plugin("second", 10, log.clone()));
+        chain.register(recording_plugin("first", 0, log.clone()));
+        chain.register(recording_plugin("third", 20, log.clone()));
+
+        chain.dispatch(test_event(1), &mut ()).await.unwrap();
+
+        assert_eq!(*log.lock().await, vec!["first", "second", "third"]);
+    }
+
+    #[tokio::test]
+    async fn a_disabled_plugin_is_skipped_until_re_enabled() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let plugin = recording_plugin("toggle-me", 0, log.clone());
+        let toggle = plugin.toggle();
+
+        let mut chain = PluginChain::<()>::new();
+        chain.register(plugin);
+
+        toggle.set_enabled(false);
+        assert_eq!(chain.active_plugins(), vec![("toggle-me", false)]);
+        chain.dispatch(test_event(1), &mut ()).await.unwrap();
+        assert!(log.lock().await.is_empty());
+
+        toggle.set_enabled(true);
+        chain.dispatch(test_event(1), &mut ()).await.unwrap();
+        assert_eq!(*log.lock().await, vec!["toggle-me"]);
+    }
+
+    #[tokio::test]
+    async fn every_action_before_done_is_forwarded_not_just_the_last_one() {
+        let mut chain = PluginChain::<()>::new();
+        chain.register(Plugin::new(
+            "replies",
+            0,
+            Handler::new(|_event: Event, _state: State<()>| async move {
+                Ok(Action::ReplyText("hi".to_string()))
+            }),
+        ));
+        chain.register(Plugin::new(
+            "stops",
+            10,
+            Handler::new(|_event: Event, _state: State<()>| async move { Ok(Action::Done) }),
+        ));
+        chain.register(Plugin::new(
+            "never-runs",
+            20,
+            Handler::new(|_event: Event, _state: State<()>| async move {
+                panic!("a plugin after Action::Done must not run")
+            }),
+        ));
+
+        let actions = chain.dispatch(test_event(1), &mut ()).await.unwrap();
+
+        assert_eq!(actions.len(), 2);
+        assert!(matches!(&actions[0], Action::ReplyText(t) if t == "hi"));
+        assert!(matches!(actions[1], Action::Done));
+    }
+}
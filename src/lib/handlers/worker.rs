@@ -0,0 +1,364 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use futures::future::BoxFuture;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::sync::{mpsc, Mutex};
+
+use crate::{Action, Event, StateStore};
+
+/// Default bound on a chat worker's inbox when a pool doesn't set its own.
+pub const DEFAULT_WORKER_BUFFER_SIZE: usize = 32;
+
+/// Default idle period after which a chat worker with an empty inbox is reaped.
+pub const DEFAULT_WORKER_IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// A handler driven by [`ChatWorkerPool`]: it owns its chat's state directly instead
+/// of sharing it behind a `State<S>` lock. The returned future borrows `state` for
+/// its own lifetime rather than `'static`, so it can actually read and mutate it.
+#[allow(clippy::type_complexity)]
+pub type WorkerHandler<S> =
+    Arc<dyn for<'a> Fn(Event, &'a mut S) -> BoxFuture<'a, Result<Action, anyhow::Error>> + Send + Sync>;
+
+/// Handle to a running chat worker, held by the pool so new events can be routed to it.
+struct ChatWorker {
+    tx: mpsc::Sender<Event>,
+}
+
+/// Spawns one long-lived task per chat id, each owning its `S` state outright and
+/// draining events from a bounded `mpsc` channel in arrival order.
+///
+/// This replaces sharing a single `Arc<RwLock<S>>` across every handler invocation:
+/// updates for the same chat are guaranteed to run in order with no lock contention,
+/// while updates for different chats run fully in parallel. The router's job is
+/// reduced to looking up (or lazily spawning) a chat's worker and calling
+/// [`ChatWorkerPool::dispatch`].
+pub struct ChatWorkerPool<S>
+where
+    S: Serialize + DeserializeOwned + Clone + Send + Sync + Default + 'static,
+{
+    workers: Arc<Mutex<HashMap<i64, ChatWorker>>>,
+    handler: WorkerHandler<S>,
+    buffer_size: usize,
+    idle_timeout: Duration,
+    store: Option<Arc<dyn StateStore<S>>>,
+}
+
+impl<S> Clone for ChatWorkerPool<S>
+where
+    S: Serialize + DeserializeOwned + Clone + Send + Sync + Default + 'static,
+{
+    fn clone(&self) -> Self {
+        Self {
+            workers: self.workers.clone(),
+            handler: self.handler.clone(),
+            buffer_size: self.buffer_size,
+            idle_timeout: self.idle_timeout,
+            store: self.store.clone(),
+        }
+    }
+}
+
+impl<S> ChatWorkerPool<S>
+where
+    S: Serialize + DeserializeOwned + Clone + Send + Sync + Default + 'static,
+{
+    /// Creates a pool that runs `handler` for every event, with the default buffer
+    /// size and idle timeout, and no persistent `StateStore`.
+    ///
+    /// `handler` must build its future with `Box::pin`, e.g.
+    /// `|event, state| Box::pin(async move { .. })`, so it can borrow `state` for the
+    /// call instead of requiring a `'static` future.
+    pub fn new<Func>(handler: Func) -> Self
+    where
+        Func: Send + Sync + 'static + for<'a> Fn(Event, &'a mut S) -> BoxFuture<'a, Result<Action, anyhow::Error>>,
+    {
+        Self {
+            workers: Arc::new(Mutex::new(HashMap::new())),
+            handler: Arc::new(handler),
+            buffer_size: DEFAULT_WORKER_BUFFER_SIZE,
+            idle_timeout: DEFAULT_WORKER_IDLE_TIMEOUT,
+            store: None,
+        }
+    }
+
+    /// Sets the bound on each chat worker's inbox, for backpressure tuning.
+    pub fn with_buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size;
+        self
+    }
+
+    /// Sets how long a chat worker may sit idle (no events) before it is reaped.
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Hydrates a chat's state from `store` the first time it is seen, and writes
+    /// state back to it after every handler call and once more when a worker is
+    /// reaped, so state survives a restart.
+    pub fn with_store(mut self, store: impl StateStore<S> + 'static) -> Self {
+        self.store = Some(Arc::new(store));
+        self
+    }
+
+    /// Routes `event` to the worker for its chat, lazily spawning one if this is the
+    /// first event seen for that chat.
+    pub async fn dispatch(&self, chat_id: i64, event: Event) -> Result<(), anyhow::Error> {
+        // Clone the sender out and drop the map guard before awaiting the send: the
+        // channel is bounded, so holding the lock across a blocked send on one chat's
+        // full inbox would stall dispatch for every other chat too.
+        let tx = {
+            let mut workers = self.workers.lock().await;
+            match workers.get(&chat_id) {
+                Some(worker) => worker.tx.clone(),
+                None => {
+                    let worker = self.spawn_worker(chat_id);
+                    let tx = worker.tx.clone();
+                    workers.insert(chat_id, worker);
+                    tx
+                }
+            }
+        };
+
+        let event = match tx.send(event).await {
+            Ok(()) => return Ok(()),
+            // The worker reaped itself between the lookup above and this send; spawn
+            // a fresh one and hand the event to that instead. Recover `event` from the
+            // `SendError` rather than reusing the moved-from value.
+            Err(e) => e.0,
+        };
+
+        let tx = {
+            let mut workers = self.workers.lock().await;
+            let worker = self.spawn_worker(chat_id);
+            let tx = worker.tx.clone();
+            workers.insert(chat_id, worker);
+            tx
+        };
+
+        tx.send(event)
+            .await
+            .map_err(|_| anyhow::anyhow!("chat {} worker exited immediately", chat_id))
+    }
+
+    fn spawn_worker(&self, chat_id: i64) -> ChatWorker {
+        let (tx, mut rx) = mpsc::channel(self.buffer_size);
+        let handler = self.handler.clone();
+        let workers = self.workers.clone();
+        let idle_timeout = self.idle_timeout;
+        let store = self.store.clone();
+
+        tokio::spawn(async move {
+            let mut state = match &store {
+                Some(store) => store.load(chat_id).await.unwrap_or_default(),
+                None => S::default(),
+            };
+            // Set once this worker has taken itself out of `workers`; from then on the
+            // map entry for `chat_id` may belong to a newer worker and is left alone.
+            let mut reaped = false;
+            loop {
+                let event = match tokio::time::timeout(idle_timeout, rx.recv()).await {
+                    Ok(Some(event)) => event,
+                    // Channel closed: the pool was dropped.
+                    Ok(None) => break,
+                    // Idle timeout elapsed with nothing to do: reap this worker. Remove
+                    // it from the map *before* anything slow (a disk-backed
+                    // store.save below can take a while) so a concurrent dispatch()
+                    // has the smallest possible window in which to still find us and
+                    // send into our about-to-close channel. Even so, a dispatch()
+                    // that already cloned our `tx` before the removal landed can
+                    // complete its send in that window, so close the channel (any
+                    // later send fails and dispatch() spawns a fresh worker) and
+                    // drain what got in before that instead of silently dropping it.
+                    Err(_) => {
+                        workers.lock().await.remove(&chat_id);
+                        reaped = true;
+                        rx.close();
+                        match rx.try_recv() {
+                            Ok(event) => event,
+                            Err(_) => break,
+                        }
+                    }
+                };
+
+                let result = (handler)(event, &mut state).await;
+
+                if let Some(store) = &store {
+                    store.save(chat_id, &state).await;
+                }
+
+                let action = match result {
+                    Ok(action) => action,
+                    Err(err) => {
+                        error!("chat {} worker handler error: {}", chat_id, err);
+                        continue;
+                    }
+                };
+
+                if let Action::Done = action {
+                    break;
+                }
+            }
+            if let Some(store) = &store {
+                store.save(chat_id, &state).await;
+            }
+            if !reaped {
+                workers.lock().await.remove(&chat_id);
+            }
+        });
+
+        ChatWorker { tx }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{Chat, Message};
+
+    fn test_event(chat_id: i64, message_id: i64) -> Event {
+        Event {
+            api: Arc::new(crate::API::new("test-token".to_string())),
+            message: crate::MessageEvent::New(Message {
+                chat: Chat {
+                    id: chat_id,
+                    ..Default::default()
+                },
+                message_id,
+                ..Default::default()
+            }),
+            replies: crate::ReplyRegistry::default(),
+            sent_messages: crate::SentMessageTracker::default(),
+        }
+    }
+
+    async fn wait_for<F: Fn() -> bool>(condition: F) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !condition() {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .expect("condition should become true before the timeout");
+    }
+
+    #[tokio::test]
+    async fn events_for_one_chat_are_handled_in_arrival_order() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let handler_log = log.clone();
+        let pool: ChatWorkerPool<()> = ChatWorkerPool::new(move |event: Event, _state: &mut ()| {
+            let log = handler_log.clone();
+            Box::pin(async move {
+                log.lock()
+                    .await
+                    .push(event.get_new_message()?.message_id);
+                Ok(Action::Next)
+            })
+        });
+
+        for i in 0..20 {
+            pool.dispatch(1, test_event(1, i)).await.unwrap();
+        }
+
+        wait_for(|| log.try_lock().map(|l| l.len() == 20).unwrap_or(false)).await;
+        assert_eq!(*log.lock().await, (0..20).collect::<Vec<i64>>());
+    }
+
+    #[tokio::test]
+    async fn events_for_different_chats_do_not_block_each_other() {
+        let (gate_tx, gate_rx) = tokio::sync::oneshot::channel::<()>();
+        let gate_rx = Arc::new(Mutex::new(Some(gate_rx)));
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let handler_log = log.clone();
+        let pool: ChatWorkerPool<()> = ChatWorkerPool::new(move |event: Event, _state: &mut ()| {
+            let log = handler_log.clone();
+            let gate_rx = gate_rx.clone();
+            Box::pin(async move {
+                let chat_id = event.chat_id()?;
+                // Chat 1's first event waits on the gate; every other chat must not
+                // be stuck behind it.
+                if chat_id == 1 {
+                    if let Some(rx) = gate_rx.lock().await.take() {
+                        let _ = rx.await;
+                    }
+                }
+                log.lock().await.push(chat_id);
+                Ok(Action::Next)
+            })
+        });
+
+        pool.dispatch(1, test_event(1, 0)).await.unwrap();
+        pool.dispatch(2, test_event(2, 0)).await.unwrap();
+
+        wait_for(|| log.try_lock().map(|l| l.contains(&2)).unwrap_or(false)).await;
+        assert!(
+            !log.lock().await.contains(&1),
+            "chat 1 should still be blocked on its gate while chat 2 runs in parallel"
+        );
+
+        gate_tx.send(()).unwrap();
+        wait_for(|| log.try_lock().map(|l| l.contains(&1)).unwrap_or(false)).await;
+    }
+
+    #[tokio::test]
+    async fn a_full_buffer_applies_backpressure_to_dispatch() {
+        let (gate_tx, gate_rx) = tokio::sync::oneshot::channel::<()>();
+        let gate_rx = Arc::new(Mutex::new(Some(gate_rx)));
+        let pool: ChatWorkerPool<()> = ChatWorkerPool::new(move |_event: Event, _state: &mut ()| {
+            let gate_rx = gate_rx.clone();
+            Box::pin(async move {
+                if let Some(rx) = gate_rx.lock().await.take() {
+                    let _ = rx.await;
+                }
+                Ok(Action::Next)
+            })
+        })
+        .with_buffer_size(1);
+
+        // The first event is picked up by the worker immediately and blocks on the
+        // gate; the buffer (capacity 1) absorbs the second. A third has nowhere to
+        // go until the first is released, so dispatch() for it must block.
+        pool.dispatch(1, test_event(1, 0)).await.unwrap();
+        pool.dispatch(1, test_event(1, 1)).await.unwrap();
+
+        let blocked_pool = pool.clone();
+        let third = tokio::spawn(async move { blocked_pool.dispatch(1, test_event(1, 2)).await });
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(
+            !third.is_finished(),
+            "dispatch should block while the chat's bounded inbox is full"
+        );
+
+        gate_tx.send(()).unwrap();
+        third.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn an_idle_worker_is_reaped_without_losing_a_later_message() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let handler_log = log.clone();
+        let pool: ChatWorkerPool<()> = ChatWorkerPool::new(move |event: Event, _state: &mut ()| {
+            let log = handler_log.clone();
+            Box::pin(async move {
+                log.lock()
+                    .await
+                    .push(event.get_new_message()?.message_id);
+                Ok(Action::Next)
+            })
+        })
+        .with_idle_timeout(Duration::from_millis(20));
+
+        pool.dispatch(1, test_event(1, 0)).await.unwrap();
+        wait_for(|| log.try_lock().map(|l| l.len() == 1).unwrap_or(false)).await;
+
+        // Let the worker sit idle past its timeout and reap itself before sending
+        // the next message for the same chat.
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert!(pool.workers.lock().await.get(&1).is_none());
+
+        pool.dispatch(1, test_event(1, 1)).await.unwrap();
+        wait_for(|| log.try_lock().map(|l| l.len() == 2).unwrap_or(false)).await;
+        assert_eq!(*log.lock().await, vec![0, 1]);
+    }
+}
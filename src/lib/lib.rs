@@ -3,12 +3,18 @@ extern crate log;
 
 pub mod api;
 pub mod client;
+pub mod handlers;
 pub mod message;
+pub mod router;
+pub mod store;
 pub mod update;
 
 pub use api::*;
 pub use client::Client;
+pub use handlers::*;
 pub use message::*;
+pub use router::Router;
+pub use store::*;
 
 pub fn init_logger() {
     // We use try_init here so it can by run by tests.
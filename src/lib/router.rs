@@ -0,0 +1,150 @@
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    api::Update, Action, ChatWorkerPool, Event, MessageEvent, PluginChain, ReplyRegistry,
+    SentMessageTracker, API,
+};
+
+/// Drives the actual update dispatch path: for every incoming `Update` it checks for
+/// a handler blocked in [`Event::get_reply`] first, and otherwise runs the chat's
+/// plugin chain through its [`ChatWorkerPool`] worker, sending whatever replies the
+/// chain produces and recording each one's id for `Action::EditLastReply`.
+///
+/// This is the piece that actually calls [`ReplyRegistry::try_deliver`],
+/// [`ChatWorkerPool::dispatch`], [`PluginChain::dispatch`] and
+/// [`SentMessageTracker::record`] — without it those are just plumbing nothing drives.
+pub struct Router<S>
+where
+    S: Serialize + DeserializeOwned + Clone + Send + Sync + Default + 'static,
+{
+    api: Arc<API>,
+    replies: ReplyRegistry,
+    sent_messages: SentMessageTracker,
+    plugins: Arc<PluginChain<S>>,
+    workers: ChatWorkerPool<S>,
+}
+
+impl<S> Router<S>
+where
+    S: Serialize + DeserializeOwned + Clone + Send + Sync + Default + 'static,
+{
+    /// Builds a router that sends through `api` and runs `plugins` for every event.
+    pub fn new(api: Arc<API>, plugins: PluginChain<S>) -> Self {
+        let replies = ReplyRegistry::default();
+        let sent_messages = SentMessageTracker::default();
+        let plugins = Arc::new(plugins);
+
+        let worker_api = api.clone();
+        let worker_plugins = plugins.clone();
+        let worker_sent_messages = sent_messages.clone();
+        let workers = ChatWorkerPool::new(
+            move |event: Event, state: &mut S| -> BoxFuture<'_, Result<Action, anyhow::Error>> {
+                let api = worker_api.clone();
+                let plugins = worker_plugins.clone();
+                let sent_messages = worker_sent_messages.clone();
+                Box::pin(async move {
+                    let actions = plugins.dispatch(event.clone(), state).await?;
+                    let mut saw_done = false;
+                    for action in actions {
+                        saw_done |= matches!(action, Action::Done);
+                        apply_action(&api, &sent_messages, &event, action).await?;
+                    }
+                    Ok(if saw_done { Action::Done } else { Action::Next })
+                })
+            },
+        );
+
+        Self {
+            api,
+            replies,
+            sent_messages,
+            plugins,
+            workers,
+        }
+    }
+
+    /// The entry point a long-poll or webhook loop should call for every `Update`.
+    pub async fn handle_update(&self, update: Update) -> Result<(), anyhow::Error> {
+        let message: MessageEvent = update.into();
+
+        let event = Event {
+            api: self.api.clone(),
+            message: message.clone(),
+            replies: self.replies.clone(),
+            sent_messages: self.sent_messages.clone(),
+        };
+
+        let chat_id = match event.chat_id() {
+            Ok(chat_id) => chat_id,
+            // MessageEvent::Unknown or a callback with no message: nothing to route.
+            Err(_) => return Ok(()),
+        };
+
+        if self.replies.try_deliver(chat_id, message).await {
+            // A handler is blocked in `Event::get_reply` for this chat and already
+            // has the update; running the normal chain for it too would process it
+            // twice.
+            return Ok(());
+        }
+
+        self.workers.dispatch(chat_id, event).await
+    }
+
+    /// The registered plugins' names and enabled state, in the order they run.
+    pub fn active_plugins(&self) -> Vec<(&str, bool)> {
+        self.plugins.active_plugins()
+    }
+}
+
+async fn apply_action(
+    api: &API,
+    sent_messages: &SentMessageTracker,
+    event: &Event,
+    action: Action,
+) -> Result<(), anyhow::Error> {
+    let chat_id = event.chat_id()?;
+    match action {
+        Action::Next | Action::Done => {}
+
+        Action::ReplyText(text) => {
+            let message = api.send_message(chat_id, text).await?;
+            sent_messages.record(chat_id, message.message_id).await;
+        }
+
+        Action::ReplyMarkdown(text) => {
+            let message = api.send_markdown(chat_id, text).await?;
+            sent_messages.record(chat_id, message.message_id).await;
+        }
+
+        Action::ReplySticker(sticker) => {
+            let message = api.send_sticker(chat_id, sticker).await?;
+            sent_messages.record(chat_id, message.message_id).await;
+        }
+
+        Action::ReplyVoice { file, caption } => {
+            let message = api.send_voice(chat_id, file, caption).await?;
+            sent_messages.record(chat_id, message.message_id).await;
+        }
+
+        Action::EditMessage { message_id, text } => {
+            api.edit_message_text(chat_id, message_id, text).await?;
+        }
+
+        Action::EditMarkdown { message_id, text } => {
+            api.edit_message_markdown(chat_id, message_id, text).await?;
+        }
+
+        Action::DeleteMessage { message_id } => {
+            api.delete_message(chat_id, message_id).await?;
+        }
+
+        Action::EditLastReply(text) => {
+            let message_id = event.last_sent_message_id().await?;
+            api.edit_message_text(chat_id, message_id, text).await?;
+        }
+    }
+    Ok(())
+}
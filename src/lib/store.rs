@@ -0,0 +1,139 @@
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::sync::Mutex;
+
+/// Persists a chat's handler state across restarts.
+///
+/// [`ChatWorkerPool`](crate::ChatWorkerPool) hydrates a chat's state from its store
+/// the first time that chat is seen, and writes it back after every handler call (and
+/// once more when the worker is reaped), so wizards and counters survive a restart.
+///
+/// Implementations must tolerate concurrent calls for different `chat_id`s, but never
+/// see concurrent calls for the *same* `chat_id`: the owning chat worker only ever
+/// touches its own state sequentially.
+#[async_trait]
+pub trait StateStore<S>: Send + Sync
+where
+    S: Serialize + DeserializeOwned + Clone + Send + Sync,
+{
+    /// Loads the saved state for `chat_id`, or `None` if nothing has been saved yet.
+    async fn load(&self, chat_id: i64) -> Option<S>;
+
+    /// Persists `state` for `chat_id`, overwriting whatever was saved before.
+    async fn save(&self, chat_id: i64, state: &S);
+}
+
+/// The default store: keeps state in memory only, so none of it survives a restart.
+#[derive(Clone)]
+pub struct MemoryStateStore<S> {
+    states: Arc<Mutex<HashMap<i64, S>>>,
+}
+
+impl<S> Default for MemoryStateStore<S> {
+    fn default() -> Self {
+        Self {
+            states: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+#[async_trait]
+impl<S> StateStore<S> for MemoryStateStore<S>
+where
+    S: Serialize + DeserializeOwned + Clone + Send + Sync,
+{
+    async fn load(&self, chat_id: i64) -> Option<S> {
+        self.states.lock().await.get(&chat_id).cloned()
+    }
+
+    async fn save(&self, chat_id: i64, state: &S) {
+        self.states.lock().await.insert(chat_id, state.clone());
+    }
+}
+
+/// Disk-backed store that serializes each chat's state as CBOR (compact and
+/// schema-flexible) into an embedded `sled` key-value database, keyed by chat id.
+pub struct SledStateStore<S> {
+    db: sled::Db,
+    _marker: std::marker::PhantomData<fn() -> S>,
+}
+
+impl<S> SledStateStore<S> {
+    /// Opens (or creates) the sled database at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, anyhow::Error> {
+        Ok(Self {
+            db: sled::open(path)?,
+            _marker: std::marker::PhantomData,
+        })
+    }
+}
+
+#[async_trait]
+impl<S> StateStore<S> for SledStateStore<S>
+where
+    S: Serialize + DeserializeOwned + Clone + Send + Sync,
+{
+    async fn load(&self, chat_id: i64) -> Option<S> {
+        let bytes = self.db.get(chat_id.to_be_bytes()).ok().flatten()?;
+        ciborium::de::from_reader(bytes.as_ref()).ok()
+    }
+
+    async fn save(&self, chat_id: i64, state: &S) {
+        let mut bytes = Vec::new();
+        if let Err(err) = ciborium::ser::into_writer(state, &mut bytes) {
+            error!("failed to encode state for chat {} as CBOR: {}", chat_id, err);
+            return;
+        }
+        if let Err(err) = self.db.insert(chat_id.to_be_bytes(), bytes) {
+            error!("failed to persist state for chat {}: {}", chat_id, err);
+            return;
+        }
+        // sled only flushes to disk periodically in the background; without an
+        // explicit flush here a crash right after `save` would lose the write,
+        // defeating the "survive a restart" point of this store.
+        if let Err(err) = self.db.flush_async().await {
+            error!("failed to flush state for chat {} to disk: {}", chat_id, err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+    struct Counter {
+        count: u32,
+    }
+
+    #[tokio::test]
+    async fn memory_store_round_trips() {
+        let store = MemoryStateStore::<Counter>::default();
+        assert_eq!(store.load(1).await, None);
+
+        store.save(1, &Counter { count: 3 }).await;
+        assert_eq!(store.load(1).await, Some(Counter { count: 3 }));
+        assert_eq!(store.load(2).await, None);
+    }
+
+    #[tokio::test]
+    async fn sled_store_round_trips_state_as_cbor() {
+        let dir = std::env::temp_dir().join(format!(
+            "mobot-sled-store-test-{}-{}",
+            std::process::id(),
+            line!()
+        ));
+        let store = SledStateStore::<Counter>::open(&dir).expect("open sled db");
+
+        assert_eq!(store.load(42).await, None);
+
+        store.save(42, &Counter { count: 7 }).await;
+        assert_eq!(store.load(42).await, Some(Counter { count: 7 }));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}